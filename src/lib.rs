@@ -1,13 +1,38 @@
 //! A bare-bones HTTP server library with dynamic page capabilties
 
-use std::borrow;
+use std::collections;
 use std::fs;
+use std::io;
 use std::io::prelude::*;
 use std::net;
 use std::path;
 use std::thread;
 
-type Function = fn() -> String;
+/// Anchor functions come in two flavours: a plain `fn() -> String` that ignores the request, and a
+/// `fn(&Request) -> String` that can tailor its output to the method, query parameters or headers
+/// of the incoming request. Both are carried by this enum so a DynamicPage can mix the two.
+#[derive(Clone)]
+#[derive(Debug)]
+pub enum Function {
+    Simple(fn() -> String),
+    WithRequest(fn(&Request) -> String),
+}
+
+/// Parsed view of an incoming HTTP request handed to request-aware anchor functions. The query
+/// parameters and header names are already decoded; header names are lower-cased for lookup.
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct Request {
+    pub method:  String,
+    pub path:    String,
+    pub query:   collections::HashMap<String, String>,
+    pub headers: collections::HashMap<String, String>,
+    pub body:    String,
+}
+
+/// Size of the blocks used when writing a response body to the socket, so large files are streamed
+/// in fixed-size pieces instead of being handed to a single write call.
+const CHUNK_SIZE: usize = 64 * 1024;
 
 
 /// Instanciates the HTTP server with specific details about address and TCP port.
@@ -23,6 +48,11 @@ pub struct Server {
     root_dir:   path::PathBuf,
     std_page:   String,
     dynamic_pages: Vec<DynamicPage>,
+    mime_types: collections::HashMap<String, String>,
+    proxies:    Vec<(String, String)>,
+    tls:        Option<(path::PathBuf, path::PathBuf)>,
+    #[cfg(feature = "tls")]
+    tls_config: Option<std::sync::Arc<rustls::ServerConfig>>,
 }
 
 /// Each instance of DynamicPage is associated with an unique URL, and can define multiple anchors.
@@ -31,6 +61,7 @@ pub struct Server {
 pub struct DynamicPage {
     pub url:     String,
     pub anchors: Vec<Anchor>,
+    pub methods: Vec<String>,
 }
 
 /// Anchor defines a string from the html page (the marker), which will trigger the execution of a
@@ -50,15 +81,55 @@ impl Server {
     pub fn new(ip_address: String, tcp_port: u32) -> Server {
 
         Server {
-            ip_address: ip_address,
-            tcp_port:   tcp_port,
+            ip_address,
+            tcp_port,
             root_dir:   path::PathBuf::from("."),
             debug:      false,
             std_page:   String::from("index.html"),
             dynamic_pages: Vec::new(),
+            mime_types: Server::default_mime_types(),
+            proxies:    Vec::new(),
+            tls:        None,
+            #[cfg(feature = "tls")]
+            tls_config: None,
         }
     }
 
+    /// Enables HTTPS by serving with the given PEM certificate chain and private key. Requires the
+    /// `tls` cargo feature; with the feature disabled the paths are stored but plain HTTP is served.
+    pub fn tls(&mut self, cert_path: path::PathBuf, key_path: path::PathBuf) {
+        self.tls = Some((cert_path, key_path));
+    }
+
+    /// Builds the default extension to content type lookup table. Any extension not present is
+    /// treated as application/octet-stream at serve time.
+    fn default_mime_types() -> collections::HashMap<String, String> {
+
+        let defaults = [
+            ("html",  "text/html"),
+            ("htm",   "text/html"),
+            ("css",   "text/css"),
+            ("js",    "application/javascript"),
+            ("json",  "application/json"),
+            ("txt",   "text/plain"),
+            ("png",   "image/png"),
+            ("jpg",   "image/jpeg"),
+            ("jpeg",  "image/jpeg"),
+            ("gif",   "image/gif"),
+            ("svg",   "image/svg+xml"),
+            ("ico",   "image/x-icon"),
+            ("wasm",  "application/wasm"),
+            ("woff",  "font/woff"),
+            ("woff2", "font/woff2"),
+            ("pdf",   "application/pdf"),
+            ("xml",   "application/xml"),
+        ];
+
+        defaults.iter()
+                .map(|(ext, mime)| (ext.to_string(), mime.to_string()))
+                .collect()
+    }
+
     /// Sets debugging (lists the URL requested, the related page and HTTP result) on and off.
     pub fn debug(&mut self, debug: bool) {
         self.debug = debug;
@@ -92,6 +163,91 @@ impl Server {
         self.dynamic_pages.push(dynamic_page);
     }
 
+    /// Overrides or adds a single extension to content type mapping (the extension is matched
+    /// case insensitively and without the leading dot, e.g. add_mime_type("md", "text/markdown")).
+    pub fn add_mime_type(&mut self, ext: String, mime_type: String) {
+        self.mime_types.insert(ext.to_lowercase(), mime_type);
+    }
+
+    /// Loads additional mappings from an /etc/mime.types-style file: each non-comment line lists a
+    /// content type followed by whitespace-separated extensions. Entries extend (and override) the
+    /// built-in table. Returns an Err if the file cannot be read.
+    pub fn load_mime_types(&mut self, path: path::PathBuf) -> Result<(), String> {
+
+        let contents = fs::read_to_string(&path)
+            .map_err(|e| format!("Could not read mime.types file: {}", e))?;
+
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') { continue; }
+
+            let mut fields = line.split_whitespace();
+            let mime_type = match fields.next() {
+                Some(mime_type) => mime_type,
+                None            => continue,
+            };
+
+            for ext in fields {
+                self.mime_types.insert(ext.to_lowercase(), mime_type.to_string());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Registers a reverse-proxy route: any request whose path starts with `prefix` is forwarded
+    /// verbatim to the `upstream` backend (given as `host:port`) and its response streamed back to
+    /// the client. Proxies are resolved before the static/dynamic file lookup, in insertion order.
+    pub fn insert_proxy(&mut self, prefix: String, upstream: String) {
+        self.proxies.push((prefix, upstream));
+    }
+
+    /// Forwards an already-read request (its header block plus any body) to the upstream backend and
+    /// streams the upstream response back to the client unchanged. Answers 502 Bad Gateway if the
+    /// upstream connection cannot be established.
+    fn handle_proxy<S: Write>(stream: &mut S, upstream: &str, header_bytes: &[u8], body: &[u8]) {
+
+        let mut backend = match net::TcpStream::connect(upstream) {
+            Ok(backend) => backend,
+            Err(_)      => {
+                let message = "502 Bad Gateway";
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\r\n{}",
+                    message, message.len(), message);
+                stream.write_all(response.as_bytes()).ok();
+                stream.flush().ok();
+                return;
+            },
+        };
+
+        backend.write_all(header_bytes).ok();
+        backend.write_all(body).ok();
+        backend.flush().ok();
+
+        let mut buffer = [0; CHUNK_SIZE];
+        loop {
+            match backend.read(&mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(n)          => {
+                    if stream.write_all(&buffer[..n]).is_err() { break; }
+                },
+            }
+        }
+
+        stream.flush().ok();
+    }
+
+    /// Resolves the content type for a resolved filesystem path from its extension, defaulting to
+    /// application/octet-stream when the extension is unknown or absent.
+    fn mime_type(&self, path: &str) -> String {
+
+        path::Path::new(path).extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| self.mime_types.get(&ext.to_lowercase()))
+            .cloned()
+            .unwrap_or_else(|| "application/octet-stream".to_string())
+    }
+
     /// Enables the Server instance to start serving static and dynamic pages according to the
     /// parameters set.
     pub fn run(&self) {
@@ -102,69 +258,373 @@ impl Server {
         eprintln!("Serving files in current directory via HTTP using port: {}",
             self.tcp_port);
 
+        // Load the TLS configuration once at startup (surfacing any cert/key error here) and share
+        // the resulting Arc across every connection, rather than re-parsing the PEM files per
+        // request.
+        #[cfg(feature = "tls")]
+        let template = {
+            let mut server = self.clone();
+            server.tls_config = server.load_tls_config();
+            server
+        };
+        #[cfg(not(feature = "tls"))]
+        let template = self.clone();
+
         for stream in listener.incoming() {
             let stream = stream.expect("Failure to read stream");
-            let server = self.clone();
-            thread::spawn(move || { Server::handle_connection(stream, &server) });
+            let peer = stream.peer_addr().map(|addr| addr.to_string()).unwrap_or_default();
+            let server = template.clone();
+            thread::spawn(move || { server.serve(stream, peer) });
         }
     }
 
-    fn handle_connection(mut stream: net::TcpStream, server: &Server) {
+    /// Hands an accepted connection to the (transport-agnostic) handler, wrapping it in a rustls
+    /// session first when TLS is enabled and the `tls` feature is compiled in. The rustls
+    /// configuration is the one built once in `run`, so only a cheap Arc clone happens per request.
+    fn serve(&self, stream: net::TcpStream, peer: String) {
+
+        #[cfg(feature = "tls")]
+        {
+            if let Some(config) = &self.tls_config {
+                if let Ok(connection) = rustls::ServerConnection::new(config.clone()) {
+                    let tls_stream = rustls::StreamOwned::new(connection, stream);
+                    Server::handle_connection(tls_stream, peer, self);
+                }
+                return;
+            }
+        }
+
+        Server::handle_connection(stream, peer, self);
+    }
+
+    /// Loads the configured certificate chain and private key into a rustls server configuration.
+    #[cfg(feature = "tls")]
+    fn load_tls_config(&self) -> Option<std::sync::Arc<rustls::ServerConfig>> {
+
+        use std::io::BufReader;
+
+        let (cert_path, key_path) = self.tls.as_ref()?;
+
+        let cert_file = fs::File::open(cert_path).expect("Could not open certificate file");
+        let certs = rustls_pemfile::certs(&mut BufReader::new(cert_file))
+            .collect::<Result<Vec<_>, _>>()
+            .expect("Invalid certificate file");
+
+        let key_file = fs::File::open(key_path).expect("Could not open private key file");
+        let key = rustls_pemfile::private_key(&mut BufReader::new(key_file))
+            .expect("Invalid private key file")
+            .expect("No private key found in key file");
+
+        let config = rustls::ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(certs, key)
+            .expect("Invalid certificate/key pair");
+
+        Some(std::sync::Arc::new(config))
+    }
+
+    fn handle_connection<S: Read + Write>(mut stream: S, peer: String, server: &Server) {
 
         let mut buffer = [0; 512];
 
-        let request_content = {
-            stream.read(&mut buffer).unwrap();
-            String::from_utf8_lossy(&buffer[..]).to_string()
+        // Read until the end-of-headers marker, so long request lines, cookies or bodies are not
+        // truncated the way a single fixed-size read would.
+        let mut raw: Vec<u8> = Vec::new();
+        let header_end = loop {
+            if let Some(pos) = Server::find_subslice(&raw, b"\r\n\r\n") {
+                break pos + 4;
+            }
+            match stream.read(&mut buffer) {
+                Ok(0) | Err(_) => break raw.len(),
+                Ok(n)          => raw.extend_from_slice(&buffer[..n]),
+            }
         };
 
-        let url = match request_content.split_whitespace().nth(1) {
-            Some(url) => url.to_string(),
-            None      => return,
+        let header_text = String::from_utf8_lossy(&raw[..header_end]).to_string();
+
+        let mut request = match Request::parse(&header_text) {
+            Some(request) => request,
+            None          => {
+                let message = "400 Bad Request";
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\r\n{}",
+                    message, message.len(), message);
+                stream.write_all(response.as_bytes()).ok();
+                stream.flush().ok();
+                return;
+            },
         };
 
+        // Having parsed the headers, honour Content-Length by reading exactly that many body bytes
+        // (some may already be sitting in `raw` after the header terminator).
+        let content_length = request.headers.get("content-length")
+            .and_then(|value| value.trim().parse::<usize>().ok())
+            .unwrap_or(0);
+
+        let mut body = raw[header_end..].to_vec();
+        while body.len() < content_length {
+            match stream.read(&mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(n)          => body.extend_from_slice(&buffer[..n]),
+            }
+        }
+        body.truncate(content_length);
+        request.body = String::from_utf8_lossy(&body).to_string();
+
+        let url = request.path.clone();
+
+        // A matching proxy prefix short-circuits the static/dynamic file lookup entirely.
+        if let Some((_, upstream)) = server.proxies.iter().find(|(prefix, _)| url.starts_with(prefix)) {
+            if server.debug { eprintln!(" {}: {} => proxy {}",
+                peer, url, upstream);
+            }
+            let header_bytes = raw[..header_end].to_vec();
+            Server::handle_proxy(&mut stream, upstream, &header_bytes, &body);
+            return;
+        }
+
+        // Now that the path is percent-decoded, a request such as /%2e%2e/%2e%2e/etc/passwd would
+        // resolve to a `..` traversal that escapes root_dir, so reject any path with a `..` segment
+        // before it ever reaches the filesystem.
+        if url.split('/').any(|segment| segment == "..") {
+            if server.debug { eprintln!(" {}: {} => 403 Forbidden", peer, url); }
+            let message = "403 Forbidden";
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\r\n{}",
+                message, message.len(), message);
+            stream.write_all(response.as_bytes()).ok();
+            stream.flush().ok();
+            return;
+        }
+
         let path = Server::get_path(&url, &server.std_page, &server.root_dir);
 
-        let (http_result, mut file_contents) = match fs::read(&path) {
-            Ok(file) => ("200 OK",        file       ),
-            Err(_)   => ("404 Not Found", Vec::new() ),
+        // Work out which methods are valid for this URL: a dynamic page declares its own set, a
+        // plain static file answers GET and HEAD. Anything else earns a 405 with an Allow header.
+        let dynamic_page = server.dynamic_pages.iter().find(|page| page.url == url).cloned();
+
+        let allowed: Vec<String> = match &dynamic_page {
+            Some(page) => page.methods.clone(),
+            None       => vec!["GET".to_string(), "HEAD".to_string()],
         };
 
-        if server.debug { eprintln!(" {}: {} = {} => {}",
-            stream.peer_addr().unwrap(), url, path, http_result);
+        if !allowed.iter().any(|method| method == &request.method) {
+            if server.debug { eprintln!(" {}: {} = {} => 405 Method Not Allowed",
+                peer, url, path);
+            }
+            let header = format!(
+                "HTTP/1.1 405 Method Not Allowed\r\nAllow: {}\r\nContent-Length: 0\r\n\r\n",
+                allowed.join(", "));
+            stream.write_all(header.as_bytes()).ok();
+            stream.flush().ok();
+            return;
         }
 
-        match server.dynamic_pages.iter().filter(|x| x.url == url)
-                                  .collect::<Vec<&DynamicPage>>().get(0) {
-            None => (),
+        let is_head = request.method == "HEAD";
+
+        match &dynamic_page {
+            // Dynamic pages must be buffered whole: the anchor substitution changes the body, so
+            // the Content-Length is only known after the replacement pass.
             Some(dynamic_page) => {
-                let mut string_file = borrow::Cow::from(String::from_utf8_lossy(&file_contents));
-                for anchor in &dynamic_page.anchors {
-                if server.debug { eprintln!(" {:?}", anchor); }
-                    if string_file.contains(&anchor.marker) {
-                        string_file = string_file.replace(&anchor.marker, &(anchor.function)())
-                                                 .into();
+                let (status, mut file_contents) = match fs::read(&path) {
+                    Ok(file) => ("200 OK",        file       ),
+                    Err(_)   => ("404 Not Found", Vec::new() ),
+                };
+
+                if server.debug { eprintln!(" {}: {} = {} => {}", peer, url, path, status); }
+
+                // HEAD must not trigger handler side effects, so the anchor functions only run for
+                // methods that actually return a body. Because the substitution changes the body
+                // length, the HEAD response omits Content-Length rather than advertise the
+                // unrendered template size, which would not match the GET body.
+                let header = if is_head {
+                    format!("HTTP/1.1 {}\r\nContent-Type: {}\r\n\r\n",
+                        status, server.mime_type(&path))
+
+                } else {
+                    let mut string_file = String::from_utf8_lossy(&file_contents);
+                    for anchor in &dynamic_page.anchors {
+                        if server.debug { eprintln!(" {:?}", anchor); }
+                        if string_file.contains(&anchor.marker) {
+                            let replacement = match &anchor.function {
+                                Function::Simple(function)      => function(),
+                                Function::WithRequest(function) => function(&request),
+                            };
+                            string_file = string_file.replace(&anchor.marker, &replacement)
+                                                     .into();
+                        }
+                    }
+                    file_contents = string_file.as_bytes().to_vec();
+
+                    format!("HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n",
+                        status, server.mime_type(&path), file_contents.len())
+                };
+
+                stream.write_all(header.as_bytes()).expect("Failure sending response");
+                if !is_head {
+                    for chunk in file_contents.chunks(CHUNK_SIZE) {
+                        stream.write_all(chunk).expect("Failure sending response");
                     }
                 }
-                file_contents = string_file.as_bytes().to_vec();
+                stream.flush().expect("Failure flushing response");
             },
+
+            // Static files are streamed straight off disk in CHUNK_SIZE blocks, so serving a large
+            // file never holds more than a single chunk in memory.
+            None => Server::serve_static(&mut stream, &path, &request, server, is_head, &peer, &url),
         }
+    }
+
+    /// Serves a static file by streaming it from disk: the file is stat'd for its size (to fill in
+    /// Content-Length / Content-Range), then read and written in CHUNK_SIZE blocks. Single-range
+    /// requests seek to the requested offset; unsatisfiable ranges answer 416 and a missing file
+    /// answers 404. For HEAD the headers are sent without the body.
+    fn serve_static<S: Write>(stream: &mut S, path: &str, request: &Request, server: &Server,
+                              is_head: bool, peer: &str, url: &str) {
+
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) if metadata.is_file() => metadata,
+            _ => {
+                if server.debug { eprintln!(" {}: {} = {} => 404 Not Found", peer, url, path); }
+                stream.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n")
+                      .expect("Failure sending response");
+                stream.flush().expect("Failure flushing response");
+                return;
+            },
+        };
 
-        let response = [
-            format!("HTTP/1.1 {}\r\n\r\n", http_result).as_bytes().to_vec(),
-            file_contents
-        ].concat();
+        let total = metadata.len() as usize;
+
+        let (status, start, length, extra_headers) = match request.headers.get("range")
+            .and_then(|range| Server::parse_range(range, total)) {
+
+            Some(Ok((start, end))) => (
+                "206 Partial Content",
+                start,
+                end - start + 1,
+                format!("Content-Range: bytes {}-{}/{}\r\n", start, end, total),
+            ),
+            Some(Err(())) => {
+                if server.debug { eprintln!(" {}: {} = {} => 416 Range Not Satisfiable",
+                    peer, url, path); }
+                let header = format!(
+                    "HTTP/1.1 416 Range Not Satisfiable\r\nContent-Range: bytes */{}\r\n\
+                     Content-Length: 0\r\n\r\n", total);
+                stream.write_all(header.as_bytes()).expect("Failure sending response");
+                stream.flush().expect("Failure flushing response");
+                return;
+            },
+            None => ("200 OK", 0, total, String::new()),
+        };
 
-        stream.write(&response).expect("Failure sending response");
+        if server.debug { eprintln!(" {}: {} = {} => {}", peer, url, path, status); }
+
+        let header = format!(
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\n{}Content-Length: {}\r\n\r\n",
+            status, server.mime_type(path), extra_headers, length);
+        stream.write_all(header.as_bytes()).expect("Failure sending response");
+
+        if !is_head {
+            Server::stream_file(stream, path, start, length);
+        }
         stream.flush().expect("Failure flushing response");
-        stream.shutdown(net::Shutdown::Both).expect("shutdown call failed");
     }
 
-    fn get_path(url: &String, std_page: &String, root_dir: &path::PathBuf) -> String {
+    /// Streams `length` bytes of a file starting at byte `start` to the client in CHUNK_SIZE reads.
+    fn stream_file<S: Write>(stream: &mut S, path: &str, start: usize, length: usize) {
+
+        let mut file = match fs::File::open(path) {
+            Ok(file) => file,
+            Err(_)   => return,
+        };
+
+        if start > 0 {
+            file.seek(io::SeekFrom::Start(start as u64)).expect("Failure seeking file");
+        }
+
+        let mut remaining = length;
+        let mut buffer = [0; CHUNK_SIZE];
+        while remaining > 0 {
+            let want = remaining.min(CHUNK_SIZE);
+            let read = file.read(&mut buffer[..want]).expect("Failure reading file");
+            if read == 0 { break; }
+            stream.write_all(&buffer[..read]).expect("Failure sending response");
+            remaining -= read;
+        }
+    }
+
+    /// Parses a single-range `Range: bytes=...` header against a known total size. Returns
+    /// `Some(Ok((start, end)))` for a satisfiable inclusive byte range, `Some(Err(()))` when the
+    /// range cannot be satisfied (so the caller can answer 416), and `None` when the header is
+    /// unparseable or names multiple ranges (so the caller can fall back to the full body).
+    fn parse_range(header: &str, total: usize) -> Option<Result<(usize, usize), ()>> {
+
+        let spec = header.strip_prefix("bytes=")?;
+        if spec.contains(',') { return None; }
+
+        let (start_str, end_str) = spec.split_once('-')?;
+
+        let last = total.saturating_sub(1);
+
+        let (start, end) = if start_str.is_empty() {
+            let suffix: usize = end_str.trim().parse().ok()?;
+            if suffix == 0 { return Some(Err(())); }
+            let suffix = suffix.min(total);
+            (total - suffix, last)
+
+        } else {
+            let start: usize = start_str.trim().parse().ok()?;
+            let end = if end_str.is_empty() {
+                last
+            } else {
+                end_str.trim().parse::<usize>().ok()?.min(last)
+            };
+            (start, end)
+        };
+
+        if total == 0 || start > last || start > end { return Some(Err(())); }
+        Some(Ok((start, end)))
+    }
+
+    /// Decodes percent-escapes (`%XX`) in a URL component. When `plus_as_space` is set a literal
+    /// `+` is also turned into a space, matching how query-string values are encoded. Invalid or
+    /// truncated escapes are passed through unchanged.
+    fn url_decode(input: &str, plus_as_space: bool) -> String {
+
+        let bytes = input.as_bytes();
+        let mut out: Vec<u8> = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+
+        while i < bytes.len() {
+            match bytes[i] {
+                b'%' if i + 2 < bytes.len() => {
+                    let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok()
+                        .filter(|h| h.bytes().all(|b| b.is_ascii_hexdigit()))
+                        .and_then(|h| u8::from_str_radix(h, 16).ok());
+
+                    match hex {
+                        Some(byte) => { out.push(byte); i += 3; },
+                        None       => { out.push(bytes[i]); i += 1; },
+                    }
+                },
+                b'+' if plus_as_space => { out.push(b' '); i += 1; },
+                byte                  => { out.push(byte); i += 1; },
+            }
+        }
+
+        String::from_utf8_lossy(&out).to_string()
+    }
+
+    /// Returns the index of the first occurrence of `needle` within `haystack`, if any.
+    fn find_subslice(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+        haystack.windows(needle.len()).position(|window| window == needle)
+    }
+
+    fn get_path(url: &String, std_page: &String, root_dir: &path::Path) -> String {
 
         let root_dir = root_dir.to_str().unwrap();
 
-        if url.chars().last().unwrap() == '/' {
+        if url.ends_with('/') {
             format!("{}{}{}", root_dir, url, std_page)
 
         } else if fs::metadata(format!("{}{}", root_dir, url)).is_ok() &&
@@ -178,6 +638,50 @@ impl Server {
 }
 
 
+impl Request {
+
+    /// Parses the request line and headers out of the raw request text. The request line is split
+    /// into method, target and version (all three are required); the target is further split into a
+    /// percent-decoded path and a map of decoded query parameters. Returns None when the request
+    /// line is malformed.
+    fn parse(raw: &str) -> Option<Request> {
+
+        let mut lines = raw.lines();
+
+        let request_line = lines.next()?;
+        let mut tokens = request_line.split_whitespace();
+        let method = tokens.next()?.to_string();
+        let target = tokens.next()?;
+        let _version = tokens.next()?;
+
+        let (path_raw, query_raw) = match target.split_once('?') {
+            Some((path, query)) => (path, query),
+            None                => (target, ""),
+        };
+        let path = Server::url_decode(path_raw, false);
+
+        let mut query = collections::HashMap::new();
+        for pair in query_raw.split('&').filter(|pair| !pair.is_empty()) {
+            let (key, value) = match pair.split_once('=') {
+                Some((key, value)) => (key, value),
+                None               => (pair, ""),
+            };
+            query.insert(Server::url_decode(key, true), Server::url_decode(value, true));
+        }
+
+        let mut headers = collections::HashMap::new();
+        for line in lines {
+            if line.is_empty() { break; }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+            }
+        }
+
+        Some(Request { method, path, query, headers, body: String::new() })
+    }
+}
+
+
 
 #[cfg(test)]
 mod tests {
@@ -193,30 +697,30 @@ mod tests {
 
         let anchor1 = crate::Anchor {
             marker: "<!-- [ls] -->".to_string(),
-            function: ls_command,
+            function: crate::Function::Simple(ls_command),
         };
 
         let dynamic_page1 = crate::DynamicPage {
             url: "/".to_string(),
             anchors: vec![anchor1],
+            methods: vec!["GET".to_string(), "HEAD".to_string()],
         };
 
         let anchor2 = crate::Anchor {
             marker: "<!-- [uptime] -->".to_string(),
-            function: uptime_command,
+            function: crate::Function::WithRequest(uptime_command),
         };
 
         let dynamic_page2 = crate::DynamicPage {
             url: "/dynamic.html".to_string(),
             anchors: vec![anchor2],
+            methods: vec!["GET".to_string(), "POST".to_string()],
         };
 
         server.insert_dynamic_page(dynamic_page1);
         server.insert_dynamic_page(dynamic_page2);
 
-        match server.root_dir(path::PathBuf::from("example_dynamic")) {
-            Ok(_) => (),
-            Err(_) => ()}
+        let _ = server.root_dir(path::PathBuf::from("example_dynamic"));
 
         server.run();
     }
@@ -226,15 +730,13 @@ mod tests {
 
         let mut server = crate::Server::new("127.0.0.1".to_string(), 8080);
         server.debug(true);
-        match server.root_dir(path::PathBuf::from("example_static")) {
-            Ok(_) => (),
-            Err(_) => ()
-        }
+        let _ = server.root_dir(path::PathBuf::from("example_static"));
         server.run();
     }
 
-    /// This is an example function. Any "void" Rust function returning an String is valid.
-    fn uptime_command() -> String {
+    /// This is an example of a request-aware function. It receives the parsed Request and can base
+    /// its output on the method, query parameters or headers.
+    fn uptime_command(_request: &crate::Request) -> String {
 
         let output = process::Command::new("sh")
             .arg("-c")